@@ -0,0 +1,163 @@
+//! Base58Check: the Bitcoin-style textual encoding used to turn raw bytes
+//! into copy-pasteable, typo-resistant strings — a version byte, the
+//! payload, and a 4-byte double-SHA256 checksum, all base58-encoded.
+
+use sha2::{Digest, Sha256};
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Base58Error {
+    InvalidChar(char),
+    TooShort,
+    BadVersion { expected: u8, got: u8 },
+    BadChecksum,
+}
+
+/// Encodes `bytes` as plain base58 (no version byte or checksum): each
+/// leading zero byte becomes a leading `'1'`, and the rest is a base-256 to
+/// base-58 conversion of the big-endian integer `bytes` represents.
+pub fn encode(bytes: &[u8]) -> String {
+    let leading_zeros = bytes.iter().take_while(|&&b| b == 0).count();
+
+    // Repeatedly divide the big-endian number in `digits` by 58, recording
+    // each remainder as a base58 digit, until the number reaches zero.
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &bytes[leading_zeros..] {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut out = String::with_capacity(leading_zeros + digits.len());
+    out.extend(std::iter::repeat('1').take(leading_zeros));
+    out.extend(digits.iter().rev().map(|&d| ALPHABET[d as usize] as char));
+    out
+}
+
+/// Inverts [`encode`].
+pub fn decode(s: &str) -> Result<Vec<u8>, Base58Error> {
+    let leading_ones = s.chars().take_while(|&c| c == '1').count();
+
+    // Repeatedly multiply the accumulated base-256 number by 58 and add in
+    // each base58 digit, building the big-endian byte representation.
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in s.chars() {
+        let digit = ALPHABET
+            .iter()
+            .position(|&a| a as char == c)
+            .ok_or(Base58Error::InvalidChar(c))? as u32;
+
+        let mut carry = digit;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+
+    let mut out = vec![0u8; leading_ones];
+    out.extend(bytes.iter().rev());
+    Ok(out)
+}
+
+fn checksum(version: u8, payload: &[u8]) -> [u8; 4] {
+    let mut versioned = Vec::with_capacity(1 + payload.len());
+    versioned.push(version);
+    versioned.extend_from_slice(payload);
+
+    let once = Sha256::digest(&versioned);
+    let twice = Sha256::digest(once);
+    twice[0..4].try_into().unwrap()
+}
+
+/// Base58Check-encodes `version || payload || checksum(version, payload)`.
+pub fn encode_check(version: u8, payload: &[u8]) -> String {
+    let mut out = Vec::with_capacity(1 + payload.len() + 4);
+    out.push(version);
+    out.extend_from_slice(payload);
+    out.extend_from_slice(&checksum(version, payload));
+    encode(&out)
+}
+
+/// Decodes a Base58Check string, verifying both the checksum and that the
+/// version byte matches `expected_version`.
+pub fn decode_check(s: &str, expected_version: u8) -> Result<Vec<u8>, Base58Error> {
+    let bytes = decode(s)?;
+    if bytes.len() < 1 + 4 {
+        return Err(Base58Error::TooShort);
+    }
+
+    let version = bytes[0];
+    let (payload, given_checksum) = bytes[1..].split_at(bytes.len() - 1 - 4);
+
+    if version != expected_version {
+        return Err(Base58Error::BadVersion {
+            expected: expected_version,
+            got: version,
+        });
+    }
+    if given_checksum != checksum(version, payload) {
+        return Err(Base58Error::BadChecksum);
+    }
+
+    Ok(payload.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_round_trips() {
+        for bytes in [
+            vec![],
+            vec![0u8],
+            vec![0u8, 0u8, 1u8],
+            vec![0xffu8; 32],
+            (0u8..=255).collect::<Vec<u8>>(),
+        ] {
+            let encoded = encode(&bytes);
+            assert_eq!(decode(&encoded).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn check_round_trips_and_rejects_corruption() {
+        let payload = [7u8; 32];
+        let encoded = encode_check(0x00, &payload);
+
+        assert_eq!(decode_check(&encoded, 0x00).unwrap(), payload.to_vec());
+        assert_eq!(
+            decode_check(&encoded, 0x01),
+            Err(Base58Error::BadVersion {
+                expected: 0x01,
+                got: 0x00
+            })
+        );
+
+        let mut corrupted = encoded.clone();
+        let last = corrupted.pop().unwrap();
+        corrupted.push(if last == '1' { '2' } else { '1' });
+        assert_eq!(
+            decode_check(&corrupted, 0x00),
+            Err(Base58Error::BadChecksum)
+        );
+    }
+
+    #[test]
+    fn decode_rejects_invalid_characters() {
+        assert_eq!(decode("0OIl"), Err(Base58Error::InvalidChar('0')));
+    }
+}
@@ -1,3 +1,8 @@
+use crate::core::base58::{self, Base58Error};
+
+/// Base58Check version byte for an `Address`'s textual form.
+pub const ADDRESS_VERSION: u8 = 0x00;
+
 #[derive(Ord, PartialEq, PartialOrd, Eq, Clone, Copy, Hash, Debug)]
 pub struct Address([u8; 32]);
 
@@ -11,6 +16,23 @@ impl Address {
     pub fn as_bytes(&self) -> &[u8; 32] {
         &self.0
     }
+
+    pub fn from_bytes(bytes: &[u8; 32]) -> Self {
+        Address(*bytes)
+    }
+
+    /// Renders this address as a copy-pasteable Base58Check string.
+    pub fn to_base58check(&self) -> String {
+        base58::encode_check(ADDRESS_VERSION, &self.0)
+    }
+
+    /// Inverts [`Address::to_base58check`], rejecting a bad checksum or a
+    /// version byte that isn't [`ADDRESS_VERSION`].
+    pub fn from_base58check(s: &str) -> Result<Self, Base58Error> {
+        let payload = base58::decode_check(s, ADDRESS_VERSION)?;
+        let bytes: [u8; 32] = payload.try_into().map_err(|_| Base58Error::TooShort)?;
+        Ok(Address(bytes))
+    }
 }
 
 pub struct PublicKey([u8; 32]);
@@ -20,3 +42,30 @@ pub struct BlockHash(pub [u8; 32]);
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct BlockHeight(pub u64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_base58check_round_trips() {
+        let addr = Address::from_bytes(&[7u8; 32]);
+        let encoded = addr.to_base58check();
+
+        assert_eq!(Address::from_base58check(&encoded), Ok(addr));
+    }
+
+    #[test]
+    fn address_from_base58check_rejects_wrong_version() {
+        let other_version_encoded =
+            base58::encode_check(ADDRESS_VERSION.wrapping_add(1), &[7u8; 32]);
+
+        assert_eq!(
+            Address::from_base58check(&other_version_encoded),
+            Err(Base58Error::BadVersion {
+                expected: ADDRESS_VERSION,
+                got: ADDRESS_VERSION.wrapping_add(1),
+            })
+        );
+    }
+}
@@ -1,6 +1,6 @@
-use crate::core::transaction;
-
 use super::basics::*;
+use super::codec::{Decodable, DecodeError, Encodable};
+use super::merkle;
 use super::state::StateRoot;
 use super::transaction::Transaction;
 use super::transaction::TxError;
@@ -8,18 +8,28 @@ use super::transaction::TxError;
 use sha2::{Digest, Sha256};
 pub const BLOCK_DOMAIN_TAG: &[u8; 7] = b"BLOCKv1";
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Block {
+    version: u32,
     parent_hash: BlockHash,
     height: BlockHeight,
+    time: u64,
+    bits: u32,
+    nonce: u64,
     txs: Vec<Transaction>,
+    merkle_root: BlockHash,
     state_root: StateRoot,
 }
 
+/// A 256-bit big-endian proof-of-work target: a block hash is valid iff,
+/// read as a big-endian integer, it is less than or equal to the target.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Target(pub [u8; 32]);
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BlockError {
-    WrongParent {
-        expected: BlockHash,
-        got: BlockHash,
+    UnknownParent {
+        parent: BlockHash,
     },
     WrongHeight {
         expected: BlockHeight,
@@ -33,23 +43,44 @@ pub enum BlockError {
         expected: StateRoot,
         got: StateRoot,
     },
+    BadBits {
+        bits: u32,
+    },
+    InsufficientWork {
+        hash: BlockHash,
+        target: Target,
+    },
 }
 
 impl Block {
     pub fn new(
+        version: u32,
         parent_hash: BlockHash,
         height: BlockHeight,
+        time: u64,
+        bits: u32,
         txs: Vec<Transaction>,
         state_root: StateRoot,
     ) -> Self {
+        let merkle_root = merkle::merkle_root(&Self::leaf_hashes(&txs));
+
         Self {
+            version,
             parent_hash,
             height,
+            time,
+            bits,
+            nonce: 0,
             txs,
+            merkle_root,
             state_root,
         }
     }
 
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     pub fn parent_hash(&self) -> BlockHash {
         self.parent_hash
     }
@@ -58,34 +89,93 @@ impl Block {
         self.height
     }
 
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
     pub fn txs(&self) -> &[Transaction] {
         &self.txs
     }
 
+    pub fn merkle_root(&self) -> BlockHash {
+        self.merkle_root
+    }
+
     pub fn state_root(&self) -> StateRoot {
         self.state_root
     }
 
+    /// Builds an inclusion proof for `self.txs()[index]` against `merkle_root()`.
+    pub fn merkle_proof(&self, index: usize) -> Vec<(BlockHash, bool)> {
+        merkle::merkle_proof(&Self::leaf_hashes(&self.txs), index)
+    }
+
+    fn leaf_hashes(txs: &[Transaction]) -> Vec<BlockHash> {
+        txs.iter()
+            .map(|tx| BlockHash(Sha256::digest(tx.canonical_bytes()).into()))
+            .collect()
+    }
+
+    /// Decodes `bits` into a 256-bit target the way Bitcoin does, returning
+    /// `None` if the compact encoding is malformed (negative mantissa or an
+    /// exponent that shifts the mantissa out of range).
+    pub fn target(&self) -> Option<Target> {
+        let exp = self.bits >> 24;
+        let mantissa = if exp <= 3 {
+            (self.bits & 0x00FF_FFFF) >> (8 * (3 - exp))
+        } else {
+            self.bits & 0x00FF_FFFF
+        };
+
+        if mantissa & 0x0080_0000 != 0 {
+            return None;
+        }
+
+        let shift_bytes = if exp <= 3 { 0 } else { exp - 3 } as usize;
+        let mantissa_bytes = mantissa.to_be_bytes();
+
+        let end = 31usize.checked_sub(shift_bytes)?;
+        let start = end.checked_sub(2)?;
+
+        let mut out = [0u8; 32];
+        out[start..=end].copy_from_slice(&mantissa_bytes[1..4]);
+        Some(Target(out))
+    }
+
     pub fn hash(&self) -> BlockHash {
-        // BLOCKv1 || parent_hash || height || tx_count || tx_0_bytes || tx_1_bytes || ... || state_root
+        // BLOCKv1 || version || parent_hash || height || time || bits || nonce
+        //         || tx_count || merkle_root || state_root
 
         let mut out = Vec::with_capacity(
             BLOCK_DOMAIN_TAG.len() // tag
+            + 4 // version
             + 32 // parent_hash
             + 8 // height
+            + 8 // time
+            + 4 // bits
+            + 8 // nonce
             + 8 // tx_count
-            + self.txs.len() * transaction::TX_CANONICAL_BYTES_LENGTH  // tx canonical_bytes
+            + 32 // merkle_root
             + 32, // StateRoot
         );
 
         out.extend_from_slice(BLOCK_DOMAIN_TAG);
+        out.extend_from_slice(&self.version.to_le_bytes());
         out.extend_from_slice(&self.parent_hash.0);
         out.extend_from_slice(&self.height.0.to_le_bytes());
+        out.extend_from_slice(&self.time.to_le_bytes());
+        out.extend_from_slice(&self.bits.to_le_bytes());
+        out.extend_from_slice(&self.nonce.to_le_bytes());
         out.extend_from_slice(&self.txs.len().to_le_bytes());
-
-        for tx in &self.txs {
-            out.extend_from_slice(&tx.canonical_bytes());
-        }
+        out.extend_from_slice(&self.merkle_root.0);
         out.extend_from_slice(&self.state_root.0);
 
         let mut hasher = Sha256::new();
@@ -95,3 +185,220 @@ impl Block {
         BlockHash(res.into())
     }
 }
+
+/// Verifies that `tx_hash` is included under `root` given an inclusion
+/// `proof` produced by [`Block::merkle_proof`].
+pub fn verify_merkle_proof(
+    tx_hash: BlockHash,
+    proof: &[(BlockHash, bool)],
+    root: BlockHash,
+) -> bool {
+    merkle::verify_merkle_proof(tx_hash, proof, root)
+}
+
+/// Increments `block.nonce` until its hash, read as a big-endian integer,
+/// is at or below `target`. `block.bits()` is not consulted; the caller
+/// supplies the target (normally `block.target()`).
+pub fn mine(block: &mut Block, target: Target) {
+    while block.hash().0 > target.0 {
+        block.nonce = block.nonce.wrapping_add(1);
+    }
+}
+
+impl Encodable for Block {
+    fn consensus_encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        out.extend_from_slice(BLOCK_DOMAIN_TAG);
+        out.extend_from_slice(&self.version.to_le_bytes());
+        out.extend_from_slice(&self.parent_hash.0);
+        out.extend_from_slice(&self.height.0.to_le_bytes());
+        out.extend_from_slice(&self.time.to_le_bytes());
+        out.extend_from_slice(&self.bits.to_le_bytes());
+        out.extend_from_slice(&self.nonce.to_le_bytes());
+        out.extend_from_slice(&(self.txs.len() as u64).to_le_bytes());
+        for tx in &self.txs {
+            out.extend_from_slice(&tx.consensus_encode());
+        }
+        out.extend_from_slice(&self.state_root.0);
+
+        out
+    }
+}
+
+impl Decodable for Block {
+    fn consensus_decode(buf: &[u8]) -> Result<(Self, usize), DecodeError> {
+        const HEADER_LEN: usize = 4 + 32 + 8 + 8 + 4 + 8 + 8; // up to and including tx_count
+
+        if buf.len() < BLOCK_DOMAIN_TAG.len() + HEADER_LEN {
+            return Err(DecodeError::UnexpectedEof);
+        }
+
+        let mut offset = 0;
+
+        if &buf[offset..offset + BLOCK_DOMAIN_TAG.len()] != BLOCK_DOMAIN_TAG.as_slice() {
+            return Err(DecodeError::BadTag);
+        }
+        offset += BLOCK_DOMAIN_TAG.len();
+
+        let version = u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap());
+        offset += 4;
+
+        let parent_hash = BlockHash(buf[offset..offset + 32].try_into().unwrap());
+        offset += 32;
+
+        let height = BlockHeight(u64::from_le_bytes(
+            buf[offset..offset + 8].try_into().unwrap(),
+        ));
+        offset += 8;
+
+        let time = u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+
+        let bits = u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap());
+        offset += 4;
+
+        let nonce = u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+
+        let tx_count = u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+
+        let remaining = (buf.len() - offset) as u64;
+        let min_bytes = tx_count.checked_mul(super::transaction::TX_CANONICAL_BYTES_LENGTH as u64);
+        if min_bytes.map_or(true, |min_bytes| min_bytes > remaining) {
+            return Err(DecodeError::BadLength);
+        }
+
+        let mut txs = Vec::with_capacity(tx_count as usize);
+        for _ in 0..tx_count {
+            let (tx, consumed) = Transaction::consensus_decode(&buf[offset..])?;
+            offset += consumed;
+            txs.push(tx);
+        }
+
+        if buf.len() < offset + 32 {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let state_root = StateRoot(buf[offset..offset + 32].try_into().unwrap());
+        offset += 32;
+
+        let merkle_root = merkle::merkle_root(&Self::leaf_hashes(&txs));
+
+        let block = Block {
+            version,
+            parent_hash,
+            height,
+            time,
+            bits,
+            nonce,
+            txs,
+            merkle_root,
+            state_root,
+        };
+
+        Ok((block, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_with_bits(bits: u32) -> Block {
+        Block::new(
+            1,
+            BlockHash([0u8; 32]),
+            BlockHeight(0),
+            0,
+            bits,
+            Vec::new(),
+            StateRoot([0u8; 32]),
+        )
+    }
+
+    #[test]
+    fn target_decodes_small_exponent() {
+        // exp=1 <= 3 right-shifts the mantissa: (0x000080) >> (8*(3-1)) == 0.
+        let target = block_with_bits(0x0100_0080)
+            .target()
+            .expect("should decode");
+        assert_eq!(target.0, [0u8; 32]);
+    }
+
+    #[test]
+    fn target_decodes_typical_exponent() {
+        // 0x1d00ffff is Bitcoin's genesis bits: mantissa 0xffff byte-shifted
+        // left so it lands at bytes [3, 4, 5] of the 32-byte target.
+        let target = block_with_bits(0x1d00_ffff)
+            .target()
+            .expect("should decode");
+        let mut expected = [0u8; 32];
+        expected[3] = 0x00;
+        expected[4] = 0xff;
+        expected[5] = 0xff;
+        assert_eq!(target.0, expected);
+    }
+
+    #[test]
+    fn bad_bits_rejected_as_negative() {
+        // exp=4, mantissa=0x800000 has its top bit set.
+        assert_eq!(block_with_bits(0x0480_0000).target(), None);
+    }
+
+    #[test]
+    fn mine_produces_hash_at_or_below_target() {
+        // exp=0x20, mantissa=0x7fffff: a very loose target so mining is fast.
+        let mut block = block_with_bits(0x207f_ffff);
+        let target = block.target().expect("should decode");
+
+        mine(&mut block, target);
+
+        assert!(block.hash().0 <= target.0);
+    }
+
+    #[test]
+    fn decode_inverts_encode() {
+        use crate::core::codec::decode_exact;
+
+        let block = block_with_bits(0x207f_ffff);
+
+        let encoded = block.consensus_encode();
+        let decoded: Block = decode_exact(&encoded).expect("round trip should decode");
+
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn decode_inverts_encode_with_transactions() {
+        use crate::core::codec::decode_exact;
+        use crate::core::transaction::{TxBody, UnsignedTransaction};
+        use ed25519_dalek::SigningKey;
+
+        let mut sk_a = SigningKey::from_bytes(&[1u8; 32]);
+        let mut sk_b = SigningKey::from_bytes(&[2u8; 32]);
+        let addr_a = Address::from_bytes(sk_a.verifying_key().as_bytes());
+        let addr_b = Address::from_bytes(sk_b.verifying_key().as_bytes());
+        let addr_c = Address::from_bytes(&[3u8; 32]);
+
+        let tx1 = UnsignedTransaction::new(TxBody::new(addr_a, addr_b, 10, 0)).sign(&mut sk_a, 1);
+        let tx2 = UnsignedTransaction::new(TxBody::new(addr_b, addr_c, 5, 0)).sign(&mut sk_b, 1);
+        let tx3 = UnsignedTransaction::new(TxBody::new(addr_a, addr_c, 1, 1)).sign(&mut sk_a, 1);
+
+        let block = Block::new(
+            1,
+            BlockHash([0u8; 32]),
+            BlockHeight(0),
+            0,
+            0x207f_ffff,
+            vec![tx1, tx2, tx3],
+            StateRoot([0u8; 32]),
+        );
+
+        let encoded = block.consensus_encode();
+        let decoded: Block = decode_exact(&encoded).expect("round trip should decode");
+
+        assert_eq!(decoded, block);
+        assert_eq!(decoded.txs().len(), 3);
+    }
+}
@@ -0,0 +1,38 @@
+/// Binary wire format counterpart to the various `canonical_bytes` methods:
+/// where those serialize one-way for hashing, `Encodable`/`Decodable` round
+/// trip so blocks and transactions can be sent over a wire or read from disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The leading domain tag didn't match what the type expects.
+    BadTag,
+    /// The buffer ran out while reading a fixed-size field.
+    UnexpectedEof,
+    /// A length- or count-prefixed field claims more data than the buffer
+    /// could possibly hold, or a fixed-size field decoded to the wrong size.
+    BadLength,
+    /// `consensus_decode` succeeded but left bytes behind that it was
+    /// expected to consume in full.
+    TrailingBytes,
+}
+
+pub trait Encodable {
+    fn consensus_encode(&self) -> Vec<u8>;
+}
+
+pub trait Decodable: Sized {
+    /// Decodes `Self` from the front of `buf`, returning the value and the
+    /// number of bytes consumed. Leftover bytes are the caller's concern
+    /// (e.g. the next item in a container); use [`decode_exact`] when none
+    /// should remain.
+    fn consensus_decode(buf: &[u8]) -> Result<(Self, usize), DecodeError>;
+}
+
+/// Decodes `buf` and requires that it be consumed exactly, so that
+/// `decode_exact::<T>(&x.consensus_encode()) == Ok(x)` holds for any `T`.
+pub fn decode_exact<T: Decodable>(buf: &[u8]) -> Result<T, DecodeError> {
+    let (value, consumed) = T::consensus_decode(buf)?;
+    if consumed != buf.len() {
+        return Err(DecodeError::TrailingBytes);
+    }
+    Ok(value)
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::core::basics::Address;
+use crate::core::state::State;
+use crate::core::transaction::{apply_tx, validate_tx, ChainId, Transaction, TxError};
+
+/// Validated-but-unconfirmed transactions, keyed by `(from, nonce)` so that
+/// at most one pending transaction can occupy a given sender/nonce slot.
+#[derive(Debug, Default)]
+pub struct Mempool {
+    txs: BTreeMap<(Address, u64), Transaction>,
+}
+
+impl Mempool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    /// Drops the queued entry for `(from, nonce)`, if any — called once a
+    /// transaction has been confirmed in a block so its slot doesn't linger
+    /// in the mempool forever.
+    pub fn remove(&mut self, from: &Address, nonce: u64) {
+        self.txs.remove(&(*from, nonce));
+    }
+
+    /// Validates `tx` against `state` (normally the chain tip's state) and,
+    /// if it passes, queues it for inclusion. Rejects stale or ahead-of-turn
+    /// nonces the same way `apply_tx` would.
+    pub fn add_tx(
+        &mut self,
+        tx: Transaction,
+        state: &State,
+        chain_id: ChainId,
+    ) -> Result<(), TxError> {
+        validate_tx(&tx, state, chain_id)?;
+        self.txs.insert((tx.from(), tx.nonce()), tx);
+        Ok(())
+    }
+
+    /// Greedily selects executable transactions starting from `state`,
+    /// highest-`amount` first, only ever taking a sender's next transaction
+    /// once its nonce matches that sender's current expected nonce — so a
+    /// sender's queued transactions are always applied consecutively.
+    pub fn select_executable(&self, state: &State, chain_id: ChainId) -> Vec<Transaction> {
+        // `self.txs` is ordered by `(from, nonce)`, so each sender's queue
+        // already comes out with ascending nonces.
+        let mut by_sender: BTreeMap<Address, VecDeque<Transaction>> = BTreeMap::new();
+        for tx in self.txs.values() {
+            by_sender
+                .entry(tx.from())
+                .or_default()
+                .push_back(tx.clone());
+        }
+
+        let mut scratch = state.clone();
+        let mut selected = Vec::new();
+
+        loop {
+            let mut best: Option<Address> = None;
+
+            for (&addr, queue) in by_sender.iter() {
+                let Some(candidate) = queue.front() else {
+                    continue;
+                };
+                let expected_nonce = scratch.nonce_of(&addr).unwrap_or(0);
+                if candidate.nonce() != expected_nonce {
+                    continue;
+                }
+
+                let better = match best {
+                    None => true,
+                    Some(current_best) => {
+                        candidate.amount() > by_sender[&current_best].front().unwrap().amount()
+                    }
+                };
+                if better {
+                    best = Some(addr);
+                }
+            }
+
+            let Some(addr) = best else { break };
+            let tx = by_sender.get_mut(&addr).unwrap().pop_front().unwrap();
+
+            if apply_tx(&mut scratch, &tx, chain_id).is_ok() {
+                selected.push(tx);
+            }
+            // Either it applied and scratch moved on to the next nonce, or
+            // the chain moved under it since it was queued; either way this
+            // sender's head has been consumed, so the loop re-checks its
+            // (now next) front transaction on the following iteration.
+        }
+
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::state::Account;
+    use crate::core::transaction::{TxBody, UnsignedTransaction};
+    use ed25519_dalek::SigningKey;
+
+    fn keypair(seed: u8) -> (SigningKey, Address) {
+        let sk = SigningKey::from_bytes(&[seed; 32]);
+        let addr = Address::from_bytes(sk.verifying_key().as_bytes());
+        (sk, addr)
+    }
+
+    fn signed_tx(sk: &mut SigningKey, to: Address, amount: u64, nonce: u64) -> Transaction {
+        UnsignedTransaction::new(TxBody::new(
+            Address::from_bytes(sk.verifying_key().as_bytes()),
+            to,
+            amount,
+            nonce,
+        ))
+        .sign(sk, 1)
+    }
+
+    fn state_with_balance(addr: Address, balance: u64) -> State {
+        let mut state = State::new();
+        state.set_account(addr, Account { balance, nonce: 0 });
+        state
+    }
+
+    #[test]
+    fn add_tx_rejects_stale_nonce() {
+        let (mut sk, addr) = keypair(1);
+        let (_, to) = keypair(2);
+        let state = {
+            let mut s = state_with_balance(addr, 100);
+            s.set_account(
+                addr,
+                Account {
+                    balance: 100,
+                    nonce: 5,
+                },
+            );
+            s
+        };
+
+        let stale = signed_tx(&mut sk, to, 1, 4);
+        let mut mempool = Mempool::new();
+        assert!(mempool.add_tx(stale, &state, 1).is_err());
+    }
+
+    #[test]
+    fn remove_drops_the_queued_entry_for_that_sender_and_nonce() {
+        let (mut sk, addr) = keypair(1);
+        let (_, to) = keypair(2);
+        let state = state_with_balance(addr, 100);
+
+        let mut mempool = Mempool::new();
+        mempool
+            .add_tx(signed_tx(&mut sk, to, 10, 0), &state, 1)
+            .unwrap();
+        assert_eq!(mempool.len(), 1);
+
+        mempool.remove(&addr, 0);
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn select_executable_respects_per_sender_nonce_order() {
+        let (mut sk, addr) = keypair(1);
+        let (_, to) = keypair(2);
+        let state = state_with_balance(addr, 100);
+
+        let mut mempool = Mempool::new();
+        // Only nonce 0 is valid against `state` right now, so it is the only
+        // transaction `add_tx` will queue for this sender.
+        mempool
+            .add_tx(signed_tx(&mut sk, to, 10, 0), &state, 1)
+            .unwrap();
+
+        let selected = mempool.select_executable(&state, 1);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].nonce(), 0);
+    }
+
+    #[test]
+    fn select_executable_prefers_higher_amount_when_multiple_senders_ready() {
+        let (mut sk_a, addr_a) = keypair(1);
+        let (mut sk_b, addr_b) = keypair(2);
+        let (_, to) = keypair(3);
+
+        let mut state = state_with_balance(addr_a, 100);
+        state.set_account(
+            addr_b,
+            Account {
+                balance: 100,
+                nonce: 0,
+            },
+        );
+
+        let mut mempool = Mempool::new();
+        mempool
+            .add_tx(signed_tx(&mut sk_a, to, 5, 0), &state, 1)
+            .unwrap();
+        mempool
+            .add_tx(signed_tx(&mut sk_b, to, 50, 0), &state, 1)
+            .unwrap();
+
+        let selected = mempool.select_executable(&state, 1);
+        assert_eq!(selected[0].amount(), 50);
+        assert_eq!(selected[1].amount(), 5);
+    }
+}
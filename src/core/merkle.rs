@@ -0,0 +1,122 @@
+use super::basics::BlockHash;
+
+use sha2::{Digest, Sha256};
+
+/// Computes a Bitcoin-style Merkle root over a list of leaf hashes.
+///
+/// An empty list of leaves roots to the all-zero hash. A single leaf is its
+/// own root. Otherwise each row is collapsed by hashing adjacent pairs,
+/// duplicating the last element when the row has odd length, until one hash
+/// remains.
+pub fn merkle_root(leaves: &[BlockHash]) -> BlockHash {
+    if leaves.is_empty() {
+        return BlockHash([0u8; 32]);
+    }
+
+    let mut row = leaves.to_vec();
+
+    while row.len() > 1 {
+        if row.len() % 2 == 1 {
+            row.push(*row.last().unwrap());
+        }
+
+        row = row
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], &pair[1]))
+            .collect();
+    }
+
+    row[0]
+}
+
+/// Builds an inclusion proof for the leaf at `index`: the sibling hash at
+/// each level from leaf to root, paired with a flag that is `true` when the
+/// sibling sits on the right.
+pub fn merkle_proof(leaves: &[BlockHash], mut index: usize) -> Vec<(BlockHash, bool)> {
+    assert!(index < leaves.len(), "merkle_proof: index out of range");
+
+    let mut proof = Vec::new();
+    let mut row = leaves.to_vec();
+
+    while row.len() > 1 {
+        if row.len() % 2 == 1 {
+            row.push(*row.last().unwrap());
+        }
+
+        let sibling_index = index ^ 1;
+        let sibling_on_right = index % 2 == 0;
+        proof.push((row[sibling_index], sibling_on_right));
+
+        row = row
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], &pair[1]))
+            .collect();
+        index /= 2;
+    }
+
+    proof
+}
+
+/// Folds a Merkle proof back up from a leaf hash and checks it against `root`.
+pub fn verify_merkle_proof(leaf: BlockHash, proof: &[(BlockHash, bool)], root: BlockHash) -> bool {
+    let mut acc = leaf;
+
+    for &(sibling, sibling_on_right) in proof {
+        acc = if sibling_on_right {
+            hash_pair(&acc, &sibling)
+        } else {
+            hash_pair(&sibling, &acc)
+        };
+    }
+
+    acc == root
+}
+
+fn hash_pair(left: &BlockHash, right: &BlockHash) -> BlockHash {
+    let mut hasher = Sha256::new();
+    hasher.update(left.0);
+    hasher.update(right.0);
+    BlockHash(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(byte: u8) -> BlockHash {
+        BlockHash([byte; 32])
+    }
+
+    #[test]
+    fn single_leaf_is_its_own_root() {
+        let leaves = vec![leaf(1)];
+        assert_eq!(merkle_root(&leaves), leaves[0]);
+    }
+
+    #[test]
+    fn odd_row_duplicates_last_element() {
+        let leaves = vec![leaf(1), leaf(2), leaf(3)];
+        let padded = vec![leaf(1), leaf(2), leaf(3), leaf(3)];
+        assert_eq!(merkle_root(&leaves), merkle_root(&padded));
+    }
+
+    #[test]
+    fn proof_verifies_for_every_leaf() {
+        let leaves = vec![leaf(1), leaf(2), leaf(3), leaf(4), leaf(5)];
+        let root = merkle_root(&leaves);
+
+        for (i, l) in leaves.iter().enumerate() {
+            let proof = merkle_proof(&leaves, i);
+            assert!(verify_merkle_proof(*l, &proof, root), "leaf {i} failed");
+        }
+    }
+
+    #[test]
+    fn proof_rejects_wrong_leaf() {
+        let leaves = vec![leaf(1), leaf(2), leaf(3), leaf(4)];
+        let root = merkle_root(&leaves);
+        let proof = merkle_proof(&leaves, 0);
+
+        assert!(!verify_merkle_proof(leaf(9), &proof, root));
+    }
+}
@@ -1,15 +1,21 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{HashMap, HashSet};
 
 use ed25519_dalek::SigningKey;
 
+use super::base58::{self, Base58Error};
 use super::block::Block;
+use super::mempool::Mempool;
 use crate::core::{
     basics::{Address, BlockHash, BlockHeight},
     block::BlockError,
-    state::{State, StateRoot},
-    transaction::ChainId,
+    state::{Account, State, StateRoot},
+    transaction::{ChainId, Transaction, TxError},
 };
 
+// Loosest possible compact target (exp=0x20, mantissa=0x7fffff), so genesis
+// and early test chains don't need to burn cycles mining.
+const GENESIS_BITS: u32 = 0x207f_ffff;
+
 // Deterministic "treasury" key for genesis.
 // Anyone who knows these bytes can sign as the treasury.
 const GENESIS_TREASURY_SK_BYTES: [u8; 32] = [
@@ -17,6 +23,11 @@ const GENESIS_TREASURY_SK_BYTES: [u8; 32] = [
     0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42,
 ];
 
+/// Base58Check version byte for an exported signing key's textual form,
+/// distinct from [`crate::core::basics::ADDRESS_VERSION`] so a pasted
+/// string can't be mistaken for the wrong kind of value.
+const SIGNING_KEY_VERSION: u8 = 0x80;
+
 pub fn genesis_treasury_signing_key() -> SigningKey {
     SigningKey::from_bytes(&GENESIS_TREASURY_SK_BYTES)
 }
@@ -27,45 +38,105 @@ pub fn genesis_treasury_address() -> Address {
     Address::from_bytes(vk.as_bytes())
 }
 
+/// Renders a signing key as a copy-pasteable Base58Check string.
+pub fn signing_key_to_base58check(sk: &SigningKey) -> String {
+    base58::encode_check(SIGNING_KEY_VERSION, &sk.to_bytes())
+}
+
+/// Inverts [`signing_key_to_base58check`].
+pub fn signing_key_from_base58check(s: &str) -> Result<SigningKey, Base58Error> {
+    let payload = base58::decode_check(s, SIGNING_KEY_VERSION)?;
+    let bytes: [u8; 32] = payload.try_into().map_err(|_| Base58Error::TooShort)?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
 fn genesis_state() -> State {
-    let mut balances = BTreeMap::new();
+    let mut state = State::new();
 
     let treasury = genesis_treasury_address();
-    balances.insert(treasury, 1);
+    state.set_account(
+        treasury,
+        Account {
+            balance: 1,
+            nonce: 0,
+        },
+    );
 
-    State {
-        balances,
-        nonces: BTreeMap::new(),
-    }
+    state
 }
 
 fn genesis_block() -> Block {
     let state_root = genesis_state().state_root();
 
-    Block::new(BlockHash([0u8; 32]), BlockHeight(0), Vec::new(), state_root)
+    let mut block = Block::new(
+        1,
+        BlockHash([0u8; 32]),
+        BlockHeight(0),
+        0,
+        GENESIS_BITS,
+        Vec::new(),
+        state_root,
+    );
+    let target = block
+        .target()
+        .expect("GENESIS_BITS is a well-formed compact target");
+    super::block::mine(&mut block, target);
+
+    block
+}
+
+/// A block together with the data derived from applying it, keyed by its own
+/// hash so that competing branches can be stored side by side.
+struct BlockRecord {
+    block: Block,
+    // Cumulative work of the chain ending at this block. Real proof-of-work
+    // weighting (2^256 / (target + 1), summed per block) is future work;
+    // height is a monotonic stand-in since every block here costs one unit.
+    cumulative_work: u128,
+    state: State,
+}
+
+/// The blocks disconnected from the old tip and connected onto the new tip
+/// by a fork-choice switch: `disconnected` newest-first, `connected`
+/// oldest-first. A block that extends the current tip has just itself in
+/// `connected`; both are empty when a block is kept as a side branch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reorg {
+    pub disconnected: Vec<BlockHash>,
+    pub connected: Vec<BlockHash>,
 }
 
 pub struct Node {
     tip_hash: BlockHash,
     tip_height: BlockHeight,
     state: State,
-    blocks: HashMap<BlockHash, Block>,
+    blocks: HashMap<BlockHash, BlockRecord>,
+    mempool: Mempool,
 }
 
 impl Node {
     pub fn new() -> Node {
         let block = genesis_block();
         let tip_height = block.height();
-        let mut blocks = HashMap::new();
         let tip_hash = block.hash();
         let state = genesis_state();
-        blocks.insert(tip_hash, block);
+
+        let mut blocks = HashMap::new();
+        blocks.insert(
+            tip_hash,
+            BlockRecord {
+                block,
+                cumulative_work: 1,
+                state: state.clone(),
+            },
+        );
 
         Node {
             tip_hash,
             tip_height,
             state,
             blocks,
+            mempool: Mempool::new(),
         }
     }
     pub fn get_tip(&self) -> (BlockHash, BlockHeight) {
@@ -77,14 +148,59 @@ impl Node {
     }
 
     pub fn get_block(&self, hash: &BlockHash) -> Option<&Block> {
-        self.blocks.get(hash)
+        self.blocks.get(hash).map(|record| &record.block)
     }
 
-    pub fn apply_block(&mut self, block: Block, chain_id: ChainId) -> Result<(), BlockError> {
-        Self::validate_blockhash(self.tip_hash, block.parent_hash())?;
-        Self::validate_blockheight(self.tip_height, block.height())?;
+    /// Validates `tx` against the current tip state and, if it passes,
+    /// queues it in the mempool for a future [`Node::build_block`].
+    pub fn add_tx_to_mempool(&mut self, tx: Transaction, chain_id: ChainId) -> Result<(), TxError> {
+        self.mempool.add_tx(tx, &self.state, chain_id)
+    }
+
+    /// Builds a ready-to-broadcast block at `tip_height + 1`: greedily
+    /// selects executable, nonce-ordered transactions from the mempool,
+    /// applies them to a cloned tip state to derive `state_root`, and mines
+    /// it against `bits` when that decodes to a target.
+    pub fn build_block(&self, chain_id: ChainId, time: u64, bits: u32) -> Block {
+        let txs = self.mempool.select_executable(&self.state, chain_id);
 
-        let mut tmp = self.state.clone();
+        let mut scratch = self.state.clone();
+        for tx in &txs {
+            super::transaction::apply_tx(&mut scratch, tx, chain_id)
+                .expect("select_executable only returns transactions that apply cleanly");
+        }
+
+        let height = BlockHeight(self.tip_height.0 + 1);
+        let state_root = scratch.state_root();
+
+        let mut block = Block::new(1, self.tip_hash, height, time, bits, txs, state_root);
+
+        if let Some(target) = block.target() {
+            super::block::mine(&mut block, target);
+        }
+
+        block
+    }
+
+    /// Accepts any block whose parent is already known, storing it alongside
+    /// any other branch. If the new block's chain now carries strictly more
+    /// cumulative work than the current tip, the tip switches to it and the
+    /// returned [`Reorg`] lists the blocks disconnected and (re)connected by
+    /// the switch; otherwise both lists are empty and the block is kept on
+    /// file as a side branch.
+    pub fn apply_block(&mut self, block: Block, chain_id: ChainId) -> Result<Reorg, BlockError> {
+        let parent_hash = block.parent_hash();
+        let parent = self
+            .blocks
+            .get(&parent_hash)
+            .ok_or(BlockError::UnknownParent {
+                parent: parent_hash,
+            })?;
+
+        Self::validate_blockheight(parent.block.height(), block.height())?;
+        Self::validate_proof_of_work(&block)?;
+
+        let mut tmp = parent.state.clone();
 
         for (index, tx) in block.txs().iter().enumerate() {
             super::transaction::apply_tx(&mut tmp, tx, chain_id)
@@ -92,26 +208,88 @@ impl Node {
         }
 
         Self::validate_stateroot(block.state_root(), tmp.state_root())?;
-        self.commit_block(tmp, block);
 
-        Ok(())
+        let cumulative_work = parent.cumulative_work + 1;
+        let block_hash = block.hash();
+        let height = block.height();
+
+        self.blocks.insert(
+            block_hash,
+            BlockRecord {
+                block,
+                cumulative_work,
+                state: tmp,
+            },
+        );
+
+        let tip_work = self.blocks[&self.tip_hash].cumulative_work;
+        let reorg = if cumulative_work > tip_work {
+            self.reorg_to(block_hash, height)
+        } else {
+            Reorg::default()
+        };
+
+        // Only blocks that are actually part of the canonical chain after
+        // this call confirm their transactions; a block that stays a side
+        // branch (empty `reorg.connected`) leaves its txs pending, since
+        // they were never applied to `self.state`.
+        let newly_confirmed: Vec<(Address, u64)> = reorg
+            .connected
+            .iter()
+            .flat_map(|hash| self.blocks[hash].block.txs().to_vec())
+            .map(|tx| (tx.from(), tx.nonce()))
+            .collect();
+        for (from, nonce) in newly_confirmed {
+            self.mempool.remove(&from, nonce);
+        }
+
+        self.requeue_disconnected(&reorg, chain_id);
+
+        Ok(reorg)
     }
 
-    fn validate_blockhash(expected: BlockHash, got: BlockHash) -> Result<(), BlockError> {
-        if expected != got {
-            return Err(BlockError::WrongParent { expected, got });
+    /// Re-offers the transactions from blocks a reorg knocked off the chain
+    /// back to the mempool against the new tip's state, so they aren't lost
+    /// just because their block stopped being canonical. Transactions that
+    /// no longer apply (already re-included on the new chain, nonce raced
+    /// past, etc.) are silently dropped rather than treated as an error.
+    /// Since the mempool only accepts a sender's next expected nonce, at
+    /// most one transaction per sender is re-queued; later ones are dropped.
+    fn requeue_disconnected(&mut self, reorg: &Reorg, chain_id: ChainId) {
+        let txs: Vec<Transaction> = reorg
+            .disconnected
+            .iter()
+            .flat_map(|hash| self.blocks[hash].block.txs().to_vec())
+            .collect();
+
+        for tx in txs {
+            let _ = self.mempool.add_tx(tx, &self.state, chain_id);
         }
-        Ok(())
     }
 
-    fn validate_blockheight(expected: BlockHeight, got: BlockHeight) -> Result<(), BlockError> {
-        let expected = BlockHeight(expected.0 + 1);
+    fn validate_blockheight(
+        parent_height: BlockHeight,
+        got: BlockHeight,
+    ) -> Result<(), BlockError> {
+        let expected = BlockHeight(parent_height.0 + 1);
         if expected != got {
             return Err(BlockError::WrongHeight { expected, got });
         }
         Ok(())
     }
 
+    fn validate_proof_of_work(block: &Block) -> Result<(), BlockError> {
+        let target = block
+            .target()
+            .ok_or(BlockError::BadBits { bits: block.bits() })?;
+        let hash = block.hash();
+
+        if hash.0 > target.0 {
+            return Err(BlockError::InsufficientWork { hash, target });
+        }
+        Ok(())
+    }
+
     fn validate_stateroot(expected: StateRoot, got: StateRoot) -> Result<(), BlockError> {
         if expected != got {
             return Err(BlockError::BadStateRoot { expected, got });
@@ -119,13 +297,282 @@ impl Node {
         Ok(())
     }
 
-    fn commit_block(&mut self, new_state: State, block: Block) {
-        self.state = new_state;
+    /// Switches the tip to `new_tip_hash`, restoring `self.state` from the
+    /// state already stored alongside it, and reports the blocks that were
+    /// disconnected from the old tip and connected onto the new one.
+    fn reorg_to(&mut self, new_tip_hash: BlockHash, new_tip_height: BlockHeight) -> Reorg {
+        let reorg = self.reorg_path(self.tip_hash, new_tip_hash);
 
-        let block_hash = block.hash();
-        self.tip_hash = block_hash;
+        self.state = self.blocks[&new_tip_hash].state.clone();
+        self.tip_hash = new_tip_hash;
+        self.tip_height = new_tip_height;
+
+        reorg
+    }
+
+    /// Walks both chains back to their most recent common ancestor, returning
+    /// the old chain's blocks newest-first (to disconnect) and the new
+    /// chain's blocks oldest-first (to connect).
+    fn reorg_path(&self, old_tip: BlockHash, new_tip: BlockHash) -> Reorg {
+        let old_chain = self.chain_to_root(old_tip);
+        let new_chain = self.chain_to_root(new_tip);
+
+        let old_set: HashSet<BlockHash> = old_chain.iter().copied().collect();
+        let fork_index = new_chain
+            .iter()
+            .position(|hash| old_set.contains(hash))
+            .expect("competing chains share at least the current tip's root ancestor");
+        let fork_point = new_chain[fork_index];
+
+        let disconnected = old_chain
+            .into_iter()
+            .take_while(|&hash| hash != fork_point)
+            .collect();
+
+        let mut connected = new_chain[..fork_index].to_vec();
+        connected.reverse();
+
+        Reorg {
+            disconnected,
+            connected,
+        }
+    }
+
+    /// The chain of block hashes from `tip` back to the oldest ancestor this
+    /// node still has on file, newest-first.
+    fn chain_to_root(&self, mut hash: BlockHash) -> Vec<BlockHash> {
+        let mut chain = Vec::new();
+
+        loop {
+            chain.push(hash);
+            let Some(record) = self.blocks.get(&hash) else {
+                break;
+            };
+            let parent = record.block.parent_hash();
+            if !self.blocks.contains_key(&parent) {
+                break;
+            }
+            hash = parent;
+        }
+
+        chain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::block::mine;
+
+    const TEST_CHAIN_ID: ChainId = 1;
+
+    /// Mines a loosely-targeted (`GENESIS_BITS`) child block so tests can
+    /// build competing branches without paying for real proof-of-work.
+    fn mined_child(
+        parent_hash: BlockHash,
+        height: BlockHeight,
+        time: u64,
+        state_root: StateRoot,
+    ) -> Block {
+        let mut block = Block::new(
+            1,
+            parent_hash,
+            height,
+            time,
+            GENESIS_BITS,
+            Vec::new(),
+            state_root,
+        );
+        let target = block
+            .target()
+            .expect("GENESIS_BITS is a well-formed compact target");
+        mine(&mut block, target);
+        block
+    }
+
+    #[test]
+    fn reorg_switches_tip_across_a_real_fork() {
+        let mut node = Node::new();
+        let (genesis_hash, genesis_height) = node.get_tip();
+        let root = node.get_state().state_root();
+        let child_height = BlockHeight(genesis_height.0 + 1);
+
+        let a1 = mined_child(genesis_hash, child_height, 1, root);
+        let a1_hash = a1.hash();
+        node.apply_block(a1, TEST_CHAIN_ID).unwrap();
+        assert_eq!(node.get_tip().0, a1_hash);
+
+        // A sibling branch, same height as a1 but a different block (and
+        // thus a different hash) since it carries a different timestamp.
+        let b1 = mined_child(genesis_hash, child_height, 2, root);
+        let b1_hash = b1.hash();
+        let tie_reorg = node.apply_block(b1, TEST_CHAIN_ID).unwrap();
+        assert_eq!(tie_reorg, Reorg::default());
+        assert_eq!(node.get_tip().0, a1_hash);
+
+        // Extending the losing branch gives it strictly more work, so the
+        // tip should now switch over to it.
+        let b2 = mined_child(b1_hash, BlockHeight(child_height.0 + 1), 3, root);
+        let b2_hash = b2.hash();
+        let reorg = node.apply_block(b2, TEST_CHAIN_ID).unwrap();
+
+        assert_eq!(node.get_tip().0, b2_hash);
+        assert_eq!(reorg.disconnected, vec![a1_hash]);
+        assert_eq!(reorg.connected, vec![b1_hash, b2_hash]);
+    }
+
+    #[test]
+    fn equal_work_does_not_switch_the_tip() {
+        let mut node = Node::new();
+        let (genesis_hash, genesis_height) = node.get_tip();
+        let root = node.get_state().state_root();
+        let child_height = BlockHeight(genesis_height.0 + 1);
+
+        let a1 = mined_child(genesis_hash, child_height, 1, root);
+        let a1_hash = a1.hash();
+        node.apply_block(a1, TEST_CHAIN_ID).unwrap();
+
+        let b1 = mined_child(genesis_hash, child_height, 2, root);
+        let reorg = node.apply_block(b1, TEST_CHAIN_ID).unwrap();
+
+        assert_eq!(reorg, Reorg::default());
+        assert_eq!(node.get_tip(), (a1_hash, child_height));
+    }
+
+    #[test]
+    fn get_block_and_get_state_reflect_the_new_tip_after_a_reorg() {
+        let mut node = Node::new();
+        let (genesis_hash, genesis_height) = node.get_tip();
+        let root = node.get_state().state_root();
+        let child_height = BlockHeight(genesis_height.0 + 1);
+
+        let a1 = mined_child(genesis_hash, child_height, 1, root);
+        node.apply_block(a1, TEST_CHAIN_ID).unwrap();
+
+        let b1 = mined_child(genesis_hash, child_height, 2, root);
+        let b1_hash = b1.hash();
+        node.apply_block(b1, TEST_CHAIN_ID).unwrap();
+
+        let b2 = mined_child(b1_hash, BlockHeight(child_height.0 + 1), 3, root);
+        let b2_hash = b2.hash();
+        node.apply_block(b2, TEST_CHAIN_ID).unwrap();
+
+        assert_eq!(node.get_tip().0, b2_hash);
+        assert_eq!(node.get_block(&b2_hash).map(Block::hash), Some(b2_hash));
+        assert_eq!(node.get_state().state_root(), root);
+    }
+
+    #[test]
+    fn apply_block_rejects_malformed_bits() {
+        let mut node = Node::new();
+        let (genesis_hash, genesis_height) = node.get_tip();
+        let root = node.get_state().state_root();
+        let child_height = BlockHeight(genesis_height.0 + 1);
+
+        // exp=4, mantissa=0x800000 has its top bit set, which Block::target()
+        // rejects as a malformed (negative) compact target.
+        let bad_bits = 0x0480_0000;
+        let block = Block::new(1, genesis_hash, child_height, 0, bad_bits, Vec::new(), root);
+
+        assert_eq!(
+            node.apply_block(block, TEST_CHAIN_ID),
+            Err(BlockError::BadBits { bits: bad_bits })
+        );
+    }
+
+    #[test]
+    fn apply_block_rejects_a_hash_above_target() {
+        let mut node = Node::new();
+        let (genesis_hash, genesis_height) = node.get_tip();
+        let root = node.get_state().state_root();
+        let child_height = BlockHeight(genesis_height.0 + 1);
+
+        // exp=1, mantissa=1 decodes to an all-zero target, which an unmined
+        // (nonce=0) block's hash can never satisfy.
+        let tight_bits = 0x0100_0001;
+        let block = Block::new(
+            1,
+            genesis_hash,
+            child_height,
+            0,
+            tight_bits,
+            Vec::new(),
+            root,
+        );
+        let target = block.target().expect("well-formed compact target");
+        let hash = block.hash();
+
+        match node.apply_block(block, TEST_CHAIN_ID) {
+            Err(BlockError::InsufficientWork {
+                hash: got_hash,
+                target: got_target,
+            }) => {
+                assert_eq!(got_hash, hash);
+                assert_eq!(got_target, target);
+            }
+            other => panic!("expected InsufficientWork, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn signing_key_base58check_round_trips() {
+        let sk = genesis_treasury_signing_key();
+        let encoded = signing_key_to_base58check(&sk);
+
+        let decoded = signing_key_from_base58check(&encoded).expect("round trip should decode");
+        assert_eq!(decoded.to_bytes(), sk.to_bytes());
+    }
+
+    #[test]
+    fn a_tx_confirmed_only_on_a_losing_side_branch_stays_in_the_mempool() {
+        use crate::core::transaction::{TxBody, UnsignedTransaction};
+
+        let mut node = Node::new();
+        let (genesis_hash, genesis_height) = node.get_tip();
+        let genesis_state = node.get_state();
+        let child_height = BlockHeight(genesis_height.0 + 1);
+
+        // Move the tip to a child of genesis so the upcoming side block,
+        // another child of genesis, ties it on cumulative work instead of
+        // winning outright.
+        let a1 = mined_child(genesis_hash, child_height, 1, genesis_state.state_root());
+        node.apply_block(a1, TEST_CHAIN_ID).unwrap();
+
+        let mut treasury_sk = genesis_treasury_signing_key();
+        let treasury = genesis_treasury_address();
+        let recipient = Address::from_bytes(&[9u8; 32]);
+        let tx = UnsignedTransaction::new(TxBody::new(treasury, recipient, 1, 0))
+            .sign(&mut treasury_sk, TEST_CHAIN_ID);
+
+        node.add_tx_to_mempool(tx.clone(), TEST_CHAIN_ID).unwrap();
+
+        let mut scratch = genesis_state.clone();
+        super::super::transaction::apply_tx(&mut scratch, &tx, TEST_CHAIN_ID).unwrap();
+        let b1 = {
+            let mut block = Block::new(
+                1,
+                genesis_hash,
+                child_height,
+                2,
+                GENESIS_BITS,
+                vec![tx.clone()],
+                scratch.state_root(),
+            );
+            let target = block
+                .target()
+                .expect("GENESIS_BITS is a well-formed compact target");
+            mine(&mut block, target);
+            block
+        };
+
+        // Same height and cumulative work as `a1`, so this stays a side
+        // branch: the tip doesn't move, and `tx` is not yet confirmed.
+        let reorg = node.apply_block(b1, TEST_CHAIN_ID).unwrap();
+        assert_eq!(reorg, Reorg::default());
 
-        self.tip_height = block.height();
-        self.blocks.insert(block_hash, block);
+        // `tx` must still be pending, since the only block that includes it
+        // never became canonical.
+        let built = node.build_block(TEST_CHAIN_ID, 3, GENESIS_BITS);
+        assert_eq!(built.txs(), &[tx]);
     }
 }
@@ -1,131 +1,321 @@
 use crate::core::basics::Address;
-use sha2::{Digest, Sha256};
-use std::collections::BTreeMap;
+use crate::core::codec::{Decodable, DecodeError, Encodable};
+use crate::core::trie::{self, Trie};
 
-#[derive(Clone, Debug, Default)]
+pub const STATE_DOMAIN_TAG: &[u8; 7] = b"STATEv1";
+
+/// An account's on-chain data: the only thing tracked per `Address`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Account {
+    pub balance: u64,
+    pub nonce: u64,
+}
+
+impl Account {
+    const ENCODED_LEN: usize = 16; // balance(u64 LE) || nonce(u64 LE)
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(Self::ENCODED_LEN);
+        out.extend_from_slice(&self.balance.to_le_bytes());
+        out.extend_from_slice(&self.nonce.to_le_bytes());
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Account> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return None;
+        }
+        Some(Account {
+            balance: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            nonce: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+        })
+    }
+}
+
+/// Account state keyed by `Address`, stored in a Merkle-Patricia trie so
+/// that `state_root()` is incremental: updating one account only re-hashes
+/// the handful of trie nodes on its path instead of the whole state.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct State {
-    pub balances: BTreeMap<Address, u64>,
-    pub nonces: BTreeMap<Address, u64>,
+    trie: Trie,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct StateRoot(pub [u8; 32]);
 
-impl State {
-    pub fn canonical_bytes(&self) -> Vec<u8> {
-        let mut out = Vec::new();
-
-        out.extend_from_slice(b"STATEv1");
+fn address_nibbles(addr: &Address) -> Vec<u8> {
+    addr.as_bytes()
+        .iter()
+        .flat_map(|&b| [b >> 4, b & 0x0f])
+        .collect()
+}
 
-        out.extend_from_slice(&(self.balances.len() as u64).to_le_bytes());
-        for (addr, bal) in self.balances.iter() {
-            out.extend_from_slice(addr.as_bytes());
-            out.extend_from_slice(&bal.to_le_bytes());
-        }
+impl State {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        out.extend_from_slice(&(self.nonces.len() as u64).to_le_bytes());
-        for (addr, nonce) in self.nonces.iter() {
-            out.extend_from_slice(addr.as_bytes());
-            out.extend_from_slice(&nonce.to_le_bytes());
-        }
+    /// StateRoot = the root hash of the account trie.
+    pub fn state_root(&self) -> StateRoot {
+        StateRoot(self.trie.root_hash())
+    }
 
-        out
+    pub fn account(&self, addr: &Address) -> Option<Account> {
+        self.trie
+            .get(&address_nibbles(addr))
+            .and_then(|bytes| Account::decode(&bytes))
     }
 
-    /// StateRoot = SHA256(canonical_bytes(state))
-    pub fn state_root(&self) -> StateRoot {
-        let bytes = self.canonical_bytes();
-        let digest = Sha256::digest(bytes);
+    pub fn balance_of(&self, addr: &Address) -> Option<u64> {
+        self.account(addr).map(|a| a.balance)
+    }
 
-        let mut out = [0u8; 32];
-        out.copy_from_slice(&digest);
-        StateRoot(out)
+    pub fn nonce_of(&self, addr: &Address) -> Option<u64> {
+        self.account(addr).map(|a| a.nonce)
     }
 
-    pub fn balance_of(&self, addr: &Address) -> Option<&u64> {
-        self.balances.get(addr)
+    /// Inserts or updates `addr`'s account, removing it from the trie
+    /// entirely once it has neither balance nor nonce left to track.
+    pub fn set_account(&mut self, addr: Address, account: Account) {
+        let nibbles = address_nibbles(&addr);
+        if account.balance == 0 && account.nonce == 0 {
+            self.trie.remove(&nibbles);
+        } else {
+            self.trie.insert(&nibbles, account.encode());
+        }
     }
 
-    pub fn nonce_of(&self, addr: &Address) -> Option<&u64> {
-        self.nonces.get(addr)
+    /// A Merkle inclusion (or non-inclusion, if `addr` has no account)
+    /// proof for `addr`'s account against `state_root()`.
+    pub fn account_proof(&self, addr: &Address) -> Vec<Vec<u8>> {
+        self.trie.proof(&address_nibbles(addr))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::Address;
-    use super::State;
+/// Stateless counterpart to [`State::account_proof`]: verifies that `addr`
+/// maps to `account` (or to nothing, when `account` is `None`) under `root`.
+pub fn verify_account_proof(
+    root: StateRoot,
+    addr: &Address,
+    account: Option<Account>,
+    proof: &[Vec<u8>],
+) -> bool {
+    trie::verify_proof(
+        root.0,
+        &address_nibbles(addr),
+        account.map(|a| a.encode()),
+        proof,
+    )
+}
 
-    const EXPECTED_CANONICAL_BYTES_HEX: &str = "53544154457631020000000000000000000000000000000000000000000000000000000000000000000000000000010a00000000000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff1400000000000000010000000000000000000000000000000000000000000000000000000000000000000000000000010000000000000000";
+fn nibbles_to_address(nibbles: &[u8]) -> Address {
+    let bytes: Vec<u8> = nibbles
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect();
+    Address::from_bytes(
+        bytes[..]
+            .try_into()
+            .expect("account keys are always 32 bytes"),
+    )
+}
 
-    const EXPECTED_STATE_ROOT_HEX: &str =
-        "ce8eb714576293f084a4f3ab758db36931136c2184c801f13bb90893bd02dbed";
+impl Encodable for State {
+    fn consensus_encode(&self) -> Vec<u8> {
+        let entries = self.trie.entries();
 
-    fn bytes_to_hex(bytes: &[u8]) -> String {
-        const HEX: &[u8; 16] = b"0123456789abcdef";
-        let mut s = String::with_capacity(bytes.len() * 2);
-        for &b in bytes {
-            s.push(HEX[(b >> 4) as usize] as char);
-            s.push(HEX[(b & 0x0f) as usize] as char);
+        let mut out = Vec::new();
+        out.extend_from_slice(STATE_DOMAIN_TAG);
+        out.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for (key, value) in entries {
+            out.extend_from_slice(nibbles_to_address(&key).as_bytes());
+            out.extend_from_slice(&value);
         }
-        s
+        out
     }
+}
+
+impl Decodable for State {
+    fn consensus_decode(buf: &[u8]) -> Result<(Self, usize), DecodeError> {
+        const ENTRY_LEN: usize = 32 + Account::ENCODED_LEN;
 
-    fn hex_to_bytes(hex: &str) -> Vec<u8> {
-        fn val(c: u8) -> u8 {
-            match c {
-                b'0'..=b'9' => c - b'0',
-                b'a'..=b'f' => c - b'a' + 10,
-                b'A'..=b'F' => c - b'A' + 10,
-                _ => panic!("Invalid hex char: {c}"),
-            }
+        if buf.len() < STATE_DOMAIN_TAG.len() {
+            return Err(DecodeError::UnexpectedEof);
         }
+        if &buf[0..STATE_DOMAIN_TAG.len()] != STATE_DOMAIN_TAG.as_slice() {
+            return Err(DecodeError::BadTag);
+        }
+        let mut offset = STATE_DOMAIN_TAG.len();
 
-        let h = hex.as_bytes();
-        assert!(h.len() % 2 == 0, "Hex length must be even");
-        let mut out = Vec::with_capacity(h.len() / 2);
-        for i in (0..h.len()).step_by(2) {
-            let hi = val(h[i]);
-            let lo = val(h[i + 1]);
-            out.push((hi << 4) | lo);
+        if buf.len() < offset + 8 {
+            return Err(DecodeError::UnexpectedEof);
         }
-        out
+        let count = u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+
+        let remaining = (buf.len() - offset) as u64;
+        let needed = count.checked_mul(ENTRY_LEN as u64);
+        if needed.map_or(true, |needed| needed > remaining) {
+            return Err(DecodeError::BadLength);
+        }
+
+        let mut state = State::new();
+        for _ in 0..count {
+            let addr = Address::from_bytes(buf[offset..offset + 32].try_into().unwrap());
+            offset += 32;
+
+            let account = Account::decode(&buf[offset..offset + Account::ENCODED_LEN])
+                .ok_or(DecodeError::BadLength)?;
+            offset += Account::ENCODED_LEN;
+
+            state.set_account(addr, account);
+        }
+
+        Ok((state, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(last_byte: u8) -> Address {
+        let mut bytes = [0u8; 32];
+        bytes[31] = last_byte;
+        Address::from_bytes(&bytes)
     }
 
     #[test]
-    fn test_state_root_vector_1() {
-        // addr_01 = 0x00..00 01
-        let mut addr_01 = [0u8; 32];
-        addr_01[31] = 1;
+    fn empty_state_root_is_all_zero() {
+        assert_eq!(State::new().state_root().0, [0u8; 32]);
+    }
 
-        // addr_ff = 0xff..ff
-        let addr_ff = [0xffu8; 32];
-        let mut s = State::default();
+    #[test]
+    fn set_account_is_visible_via_account_and_balance_of() {
+        let mut s = State::new();
+        s.set_account(
+            addr(1),
+            Account {
+                balance: 10,
+                nonce: 3,
+            },
+        );
 
-        // reverse order as in BTreeMap
-        s.balances.insert(Address(addr_ff), 20);
-        s.balances.insert(Address(addr_01), 10);
+        assert_eq!(s.balance_of(&addr(1)), Some(10));
+        assert_eq!(s.nonce_of(&addr(1)), Some(3));
+        assert_eq!(s.balance_of(&addr(2)), None);
+    }
 
-        s.nonces.insert(Address(addr_01), 0);
+    #[test]
+    fn zeroing_out_an_account_removes_it() {
+        let mut s = State::new();
+        s.set_account(
+            addr(1),
+            Account {
+                balance: 10,
+                nonce: 0,
+            },
+        );
+        s.set_account(
+            addr(1),
+            Account {
+                balance: 0,
+                nonce: 0,
+            },
+        );
 
-        let cb = s.canonical_bytes();
-        assert_eq!(cb.len(), 143, "canonical_bytes_len mismatch");
+        assert_eq!(s.account(&addr(1)), None);
+        assert_eq!(s.state_root().0, [0u8; 32]);
+    }
 
-        let cb_hex = bytes_to_hex(&cb);
-        assert_eq!(
-            cb_hex, EXPECTED_CANONICAL_BYTES_HEX,
-            "canonical_bytes hex mismatch"
+    #[test]
+    fn state_root_is_order_independent_and_changes_with_content() {
+        let mut a = State::new();
+        a.set_account(
+            addr(1),
+            Account {
+                balance: 10,
+                nonce: 0,
+            },
+        );
+        a.set_account(
+            addr(2),
+            Account {
+                balance: 20,
+                nonce: 0,
+            },
+        );
+
+        let mut b = State::new();
+        b.set_account(
+            addr(2),
+            Account {
+                balance: 20,
+                nonce: 0,
+            },
+        );
+        b.set_account(
+            addr(1),
+            Account {
+                balance: 10,
+                nonce: 0,
+            },
         );
 
+        assert_eq!(a.state_root(), b.state_root());
+        assert_ne!(a.state_root(), State::new().state_root());
+    }
+
+    #[test]
+    fn account_proof_verifies_inclusion_and_absence() {
+        let mut s = State::new();
+        let account = Account {
+            balance: 10,
+            nonce: 3,
+        };
+        s.set_account(addr(1), account);
+
         let root = s.state_root();
-        let root_hex = bytes_to_hex(&root.0);
-        assert_eq!(root_hex, EXPECTED_STATE_ROOT_HEX, "state_root hex mismatch");
-
-        // Optional: sanity-check that the root matches hashing of the expected bytes.
-        let expected_bytes = hex_to_bytes(EXPECTED_CANONICAL_BYTES_HEX);
-        assert_eq!(
-            cb, expected_bytes,
-            "canonical_bytes differ from decoded expected bytes"
+
+        let proof = s.account_proof(&addr(1));
+        assert!(verify_account_proof(root, &addr(1), Some(account), &proof));
+        assert!(!verify_account_proof(
+            root,
+            &addr(1),
+            Some(Account {
+                balance: 11,
+                nonce: 3
+            }),
+            &proof
+        ));
+
+        let missing_proof = s.account_proof(&addr(9));
+        assert!(verify_account_proof(root, &addr(9), None, &missing_proof));
+    }
+
+    #[test]
+    fn decode_inverts_encode() {
+        use crate::core::codec::decode_exact;
+
+        let mut s = State::new();
+        s.set_account(
+            addr(1),
+            Account {
+                balance: 10,
+                nonce: 0,
+            },
+        );
+        s.set_account(
+            addr(255),
+            Account {
+                balance: 20,
+                nonce: 0,
+            },
         );
+
+        let encoded = s.consensus_encode();
+        let decoded: State = decode_exact(&encoded).expect("round trip should decode");
+
+        assert_eq!(decoded, s);
     }
 }
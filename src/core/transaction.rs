@@ -1,10 +1,11 @@
 use super::basics::Address;
-use super::state::State;
+use super::codec::{Decodable, DecodeError, Encodable};
+use super::state::{Account, State};
 
+use ed25519_dalek::ed25519::signature::SignerMut;
 use ed25519_dalek::Signature as ed25519_signature;
 use ed25519_dalek::SigningKey;
 use ed25519_dalek::VerifyingKey;
-use ed25519_dalek::ed25519::signature::SignerMut;
 
 pub type ChainId = u64;
 
@@ -55,7 +56,7 @@ pub enum TxError {
     // etc
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct TxBody {
     from: Address,
     to: Address,
@@ -64,6 +65,15 @@ pub struct TxBody {
 }
 
 impl TxBody {
+    pub fn new(from: Address, to: Address, amount: u64, nonce: u64) -> Self {
+        TxBody {
+            from,
+            to,
+            amount,
+            nonce,
+        }
+    }
+
     pub fn signing_bytes(&self, chain_id: ChainId) -> Vec<u8> {
         let mut out = Vec::with_capacity(TX_SIGNING_BYTES_LENGTH);
 
@@ -83,6 +93,10 @@ pub struct UnsignedTransaction {
 }
 
 impl UnsignedTransaction {
+    pub fn new(body: TxBody) -> Self {
+        UnsignedTransaction { body }
+    }
+
     pub fn sign(&self, signing_key: &mut SigningKey, chain_id: ChainId) -> Transaction {
         let msg = &self.body.signing_bytes(chain_id);
         let signature = signing_key.sign(msg).to_bytes();
@@ -94,12 +108,29 @@ impl UnsignedTransaction {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Transaction {
     body: TxBody,
     signature: Signature,
 }
 
 impl Transaction {
+    pub fn from(&self) -> Address {
+        self.body.from
+    }
+
+    pub fn to(&self) -> Address {
+        self.body.to
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.body.amount
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.body.nonce
+    }
+
     pub fn verify_signature(
         &self,
         chain_id: ChainId,
@@ -133,6 +164,55 @@ impl Transaction {
     }
 }
 
+impl Encodable for Transaction {
+    fn consensus_encode(&self) -> Vec<u8> {
+        self.canonical_bytes()
+    }
+}
+
+impl Decodable for Transaction {
+    fn consensus_decode(buf: &[u8]) -> Result<(Self, usize), DecodeError> {
+        if buf.len() < TX_CANONICAL_BYTES_LENGTH {
+            return Err(DecodeError::UnexpectedEof);
+        }
+
+        let mut offset = 0;
+
+        if &buf[offset..offset + TX_DOMAIN_TAG.len()] != TX_DOMAIN_TAG.as_slice() {
+            return Err(DecodeError::BadTag);
+        }
+        offset += TX_DOMAIN_TAG.len();
+
+        let from = Address::from_bytes(buf[offset..offset + 32].try_into().unwrap());
+        offset += 32;
+
+        let to = Address::from_bytes(buf[offset..offset + 32].try_into().unwrap());
+        offset += 32;
+
+        let amount = u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+
+        let nonce = u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+
+        let signature =
+            Signature::try_from(&buf[offset..offset + 64]).map_err(|_| DecodeError::BadLength)?;
+        offset += 64;
+
+        let tx = Transaction {
+            body: TxBody {
+                from,
+                to,
+                amount,
+                nonce,
+            },
+            signature,
+        };
+
+        Ok((tx, offset))
+    }
+}
+
 pub fn validate_tx(tx: &Transaction, state: &State, chain_id: ChainId) -> Result<(), TxError> {
     let from = tx.body.from;
     let to = tx.body.to;
@@ -148,7 +228,7 @@ pub fn validate_tx(tx: &Transaction, state: &State, chain_id: ChainId) -> Result
         return Err(TxError::ZeroTransactionForbidden);
     }
 
-    let balance = *state.balances.get(&from).unwrap_or(&0u64);
+    let balance = state.balance_of(&from).unwrap_or(0);
 
     if balance < amount {
         let err = TxError::InsufficientFunds {
@@ -158,7 +238,7 @@ pub fn validate_tx(tx: &Transaction, state: &State, chain_id: ChainId) -> Result
         return Err(err);
     }
 
-    let addr_nonce = *state.nonces.get(&from).unwrap_or(&0u64);
+    let addr_nonce = state.nonce_of(&from).unwrap_or(0);
 
     if nonce != addr_nonce {
         let err = TxError::BadNonce {
@@ -178,26 +258,75 @@ pub fn apply_tx(state: &mut State, tx: &Transaction, chain_id: ChainId) -> Resul
     let amount = tx.body.amount;
     let nonce = tx.body.nonce;
 
-    let from_balance = *state.balances.get(&from).unwrap_or(&0u64);
-    let to_balance = *state.balances.get(&to).unwrap_or(&0u64);
+    let from_account = state.account(&from).unwrap_or_default();
+    let to_account = state.account(&to).unwrap_or_default();
+
+    let new_from_balance = from_account.balance - amount;
+    let new_to_balance = to_account
+        .balance
+        .checked_add(amount)
+        .ok_or(TxError::Overflow)?;
+    let new_from_nonce = nonce.checked_add(1).ok_or(TxError::Overflow)?;
+
+    state.set_account(
+        from,
+        Account {
+            balance: new_from_balance,
+            nonce: new_from_nonce,
+        },
+    );
+    state.set_account(
+        to,
+        Account {
+            balance: new_to_balance,
+            nonce: to_account.nonce,
+        },
+    );
+
+    Ok(())
+}
 
-    let new_from = from_balance - amount;
-    let new_to = to_balance.checked_add(amount).ok_or(TxError::Overflow)?;
-    let new_nonce = nonce.checked_add(1).ok_or(TxError::Overflow)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::codec::decode_exact;
+
+    #[test]
+    fn decode_inverts_encode() {
+        let tx = Transaction {
+            body: TxBody {
+                from: Address::from_bytes(&[1u8; 32]),
+                to: Address::from_bytes(&[2u8; 32]),
+                amount: 7,
+                nonce: 3,
+            },
+            signature: Signature([9u8; 64]),
+        };
 
-    state.nonces.insert(from, new_nonce);
+        let encoded = tx.consensus_encode();
+        assert_eq!(encoded.len(), TX_CANONICAL_BYTES_LENGTH);
 
-    if new_from == 0 {
-        state.balances.remove(&from);
-    } else {
-        state.balances.insert(from, new_from);
+        let decoded: Transaction = decode_exact(&encoded).expect("round trip should decode");
+        assert_eq!(decoded, tx);
     }
 
-    if new_to == 0 {
-        state.balances.remove(&to);
-    } else {
-        state.balances.insert(to, new_to);
-    }
+    #[test]
+    fn decode_rejects_bad_tag() {
+        let mut encoded = Transaction {
+            body: TxBody {
+                from: Address::from_bytes(&[1u8; 32]),
+                to: Address::from_bytes(&[2u8; 32]),
+                amount: 7,
+                nonce: 3,
+            },
+            signature: Signature([9u8; 64]),
+        }
+        .consensus_encode();
+        encoded[0] = b'X';
 
-    Ok(())
+        assert_eq!(
+            decode_exact::<Transaction>(&encoded),
+            Err(DecodeError::BadTag)
+        );
+    }
 }
@@ -0,0 +1,757 @@
+//! A minimal Merkle-Patricia trie (as in Ethereum) over nibble-path keys.
+//!
+//! Nodes are content-addressed: each node's hash is `SHA256` of its own
+//! canonical encoding, and nodes live in a `BTreeMap` keyed by that hash so
+//! that updating one key only ever re-hashes the handful of nodes on its
+//! path, not the whole trie.
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+const TAG_LEAF: u8 = 0;
+const TAG_EXTENSION: u8 = 1;
+const TAG_BRANCH: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: [u8; 32],
+    },
+    Branch {
+        children: [Option<[u8; 32]>; 16],
+        value: Option<Vec<u8>>,
+    },
+}
+
+impl Node {
+    /// Canonical byte layout that gets SHA256'd into this node's hash, and
+    /// that a verifier without access to the node store can parse back.
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        match self {
+            Node::Leaf { path, value } => {
+                out.push(TAG_LEAF);
+                let hp = hp_encode(path, true);
+                out.extend_from_slice(&(hp.len() as u32).to_le_bytes());
+                out.extend_from_slice(&hp);
+                out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                out.extend_from_slice(value);
+            }
+            Node::Extension { path, child } => {
+                out.push(TAG_EXTENSION);
+                let hp = hp_encode(path, false);
+                out.extend_from_slice(&(hp.len() as u32).to_le_bytes());
+                out.extend_from_slice(&hp);
+                out.extend_from_slice(child);
+            }
+            Node::Branch { children, value } => {
+                out.push(TAG_BRANCH);
+                for child in children {
+                    match child {
+                        Some(hash) => {
+                            out.push(1);
+                            out.extend_from_slice(hash);
+                        }
+                        None => out.push(0),
+                    }
+                }
+                match value {
+                    Some(v) => {
+                        out.push(1);
+                        out.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                        out.extend_from_slice(v);
+                    }
+                    None => out.push(0),
+                }
+            }
+        }
+
+        out
+    }
+
+    fn decode(buf: &[u8]) -> Option<Node> {
+        let (&tag, rest) = buf.split_first()?;
+        match tag {
+            TAG_LEAF => {
+                let (hp, rest) = take_len_prefixed(rest)?;
+                let (path, is_leaf) = hp_decode(hp)?;
+                if !is_leaf {
+                    return None;
+                }
+                let (value, rest) = take_len_prefixed(rest)?;
+                if !rest.is_empty() {
+                    return None;
+                }
+                Some(Node::Leaf {
+                    path,
+                    value: value.to_vec(),
+                })
+            }
+            TAG_EXTENSION => {
+                let (hp, rest) = take_len_prefixed(rest)?;
+                let (path, is_leaf) = hp_decode(hp)?;
+                if is_leaf || rest.len() != 32 {
+                    return None;
+                }
+                let child: [u8; 32] = rest.try_into().ok()?;
+                Some(Node::Extension { path, child })
+            }
+            TAG_BRANCH => {
+                let mut children: [Option<[u8; 32]>; 16] = [None; 16];
+                let mut rest = rest;
+                for child in children.iter_mut() {
+                    let (&flag, tail) = rest.split_first()?;
+                    rest = tail;
+                    if flag == 1 {
+                        if rest.len() < 32 {
+                            return None;
+                        }
+                        let (hash, tail) = rest.split_at(32);
+                        *child = Some(hash.try_into().ok()?);
+                        rest = tail;
+                    } else if flag != 0 {
+                        return None;
+                    }
+                }
+
+                let (&flag, rest) = rest.split_first()?;
+                let value = if flag == 1 {
+                    let (value, rest) = take_len_prefixed(rest)?;
+                    if !rest.is_empty() {
+                        return None;
+                    }
+                    Some(value.to_vec())
+                } else if flag == 0 {
+                    if !rest.is_empty() {
+                        return None;
+                    }
+                    None
+                } else {
+                    return None;
+                };
+
+                Some(Node::Branch { children, value })
+            }
+            _ => None,
+        }
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        Sha256::digest(self.encode()).into()
+    }
+}
+
+fn take_len_prefixed(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    if buf.len() < 4 {
+        return None;
+    }
+    let len = u32::from_le_bytes(buf[0..4].try_into().ok()?) as usize;
+    let rest = &buf[4..];
+    if rest.len() < len {
+        return None;
+    }
+    Some(rest.split_at(len))
+}
+
+/// Hex-prefix encodes a nibble path: packs it two-nibbles-per-byte after a
+/// leading flag nibble (odd-length flag bit + leaf/extension flag bit),
+/// padded with a zero nibble when the path has even length.
+fn hp_encode(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+    let is_odd = nibbles.len() % 2 == 1;
+    let flag = (is_leaf as u8) * 2 + (is_odd as u8);
+
+    let mut padded = Vec::with_capacity(nibbles.len() + 2);
+    padded.push(flag);
+    if !is_odd {
+        padded.push(0);
+    }
+    padded.extend_from_slice(nibbles);
+
+    padded
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect()
+}
+
+fn hp_decode(bytes: &[u8]) -> Option<(Vec<u8>, bool)> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let mut nibbles = Vec::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        nibbles.push(b >> 4);
+        nibbles.push(b & 0x0f);
+    }
+
+    let flag = nibbles[0];
+    let is_leaf = flag & 2 != 0;
+    let is_odd = flag & 1 != 0;
+    let path = if is_odd {
+        nibbles[1..].to_vec()
+    } else {
+        nibbles[2..].to_vec()
+    };
+
+    Some((path, is_leaf))
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// A Merkle-Patricia trie over nibble-path keys, with an immutable,
+/// content-addressed node store so that only the nodes on an updated key's
+/// path are ever re-hashed.
+#[derive(Clone, Debug, Default)]
+pub struct Trie {
+    root: Option<[u8; 32]>,
+    nodes: BTreeMap<[u8; 32], Node>,
+}
+
+/// Two tries are equal when they share a root hash, i.e. hold the same
+/// entries; nodes left in the store by earlier versions are not compared.
+impl PartialEq for Trie {
+    fn eq(&self, other: &Self) -> bool {
+        self.root == other.root
+    }
+}
+
+impl Trie {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The all-zero hash stands in for the empty trie, matching the
+    /// all-zero sentinels used elsewhere for "nothing here yet".
+    pub fn root_hash(&self) -> [u8; 32] {
+        self.root.unwrap_or([0u8; 32])
+    }
+
+    pub fn get(&self, nibbles: &[u8]) -> Option<Vec<u8>> {
+        self.get_at(self.root, nibbles)
+    }
+
+    fn get_at(&self, node_hash: Option<[u8; 32]>, nibbles: &[u8]) -> Option<Vec<u8>> {
+        let node = self.nodes.get(&node_hash?)?;
+        match node {
+            Node::Leaf { path, value } => (path == nibbles).then(|| value.clone()),
+            Node::Extension { path, child } => {
+                let rest = nibbles.strip_prefix(path.as_slice())?;
+                self.get_at(Some(*child), rest)
+            }
+            Node::Branch { children, value } => match nibbles.split_first() {
+                None => value.clone(),
+                Some((&nibble, rest)) => self.get_at(children[nibble as usize], rest),
+            },
+        }
+    }
+
+    pub fn insert(&mut self, nibbles: &[u8], value: Vec<u8>) {
+        self.root = Some(self.insert_at(self.root, nibbles, value));
+    }
+
+    fn put_node(&mut self, node: Node) -> [u8; 32] {
+        let hash = node.hash();
+        self.nodes.insert(hash, node);
+        hash
+    }
+
+    fn insert_at(
+        &mut self,
+        node_hash: Option<[u8; 32]>,
+        nibbles: &[u8],
+        value: Vec<u8>,
+    ) -> [u8; 32] {
+        let Some(node_hash) = node_hash else {
+            return self.put_node(Node::Leaf {
+                path: nibbles.to_vec(),
+                value,
+            });
+        };
+
+        let node = self
+            .nodes
+            .get(&node_hash)
+            .cloned()
+            .expect("trie referenced a node hash that isn't in the store");
+
+        match node {
+            Node::Leaf {
+                path: leaf_path,
+                value: leaf_value,
+            } => {
+                if leaf_path == nibbles {
+                    return self.put_node(Node::Leaf {
+                        path: leaf_path,
+                        value,
+                    });
+                }
+                self.split(&leaf_path, Some(leaf_value), nibbles, value)
+            }
+            Node::Extension {
+                path: ext_path,
+                child,
+            } => {
+                if let Some(rest) = nibbles.strip_prefix(ext_path.as_slice()) {
+                    let new_child = self.insert_at(Some(child), rest, value);
+                    return self.put_node(Node::Extension {
+                        path: ext_path,
+                        child: new_child,
+                    });
+                }
+                self.split_extension(&ext_path, child, nibbles, value)
+            }
+            Node::Branch {
+                mut children,
+                value: branch_value,
+            } => match nibbles.split_first() {
+                None => self.put_node(Node::Branch {
+                    children,
+                    value: Some(value),
+                }),
+                Some((&nibble, rest)) => {
+                    let new_child = self.insert_at(children[nibble as usize], rest, value);
+                    children[nibble as usize] = Some(new_child);
+                    self.put_node(Node::Branch {
+                        children,
+                        value: branch_value,
+                    })
+                }
+            },
+        }
+    }
+
+    /// Splits a leaf at the point it diverges from `nibbles`, producing a
+    /// branch (and, if the shared prefix is non-empty, an extension on top).
+    fn split(
+        &mut self,
+        existing_path: &[u8],
+        existing_value: Option<Vec<u8>>,
+        nibbles: &[u8],
+        value: Vec<u8>,
+    ) -> [u8; 32] {
+        let common = common_prefix_len(existing_path, nibbles);
+        let mut children: [Option<[u8; 32]>; 16] = [None; 16];
+        let mut branch_value = None;
+
+        match existing_path[common..].split_first() {
+            None => branch_value = existing_value,
+            Some((&nibble, rest)) => {
+                let hash = self.put_node(Node::Leaf {
+                    path: rest.to_vec(),
+                    value: existing_value.expect("leaf always carries a value"),
+                });
+                children[nibble as usize] = Some(hash);
+            }
+        }
+
+        match nibbles[common..].split_first() {
+            None => branch_value = Some(value),
+            Some((&nibble, rest)) => {
+                let hash = self.put_node(Node::Leaf {
+                    path: rest.to_vec(),
+                    value,
+                });
+                children[nibble as usize] = Some(hash);
+            }
+        }
+
+        let branch_hash = self.put_node(Node::Branch {
+            children,
+            value: branch_value,
+        });
+
+        if common == 0 {
+            branch_hash
+        } else {
+            self.put_node(Node::Extension {
+                path: existing_path[..common].to_vec(),
+                child: branch_hash,
+            })
+        }
+    }
+
+    /// Splits an extension at the point it diverges from `nibbles`.
+    fn split_extension(
+        &mut self,
+        ext_path: &[u8],
+        ext_child: [u8; 32],
+        nibbles: &[u8],
+        value: Vec<u8>,
+    ) -> [u8; 32] {
+        let common = common_prefix_len(ext_path, nibbles);
+        let mut children: [Option<[u8; 32]>; 16] = [None; 16];
+
+        // The extension's remainder always has at least one nibble left
+        // (a full match would have taken the strip_prefix branch instead).
+        let (&ext_nibble, ext_rest) = ext_path[common..]
+            .split_first()
+            .expect("extension must diverge from nibbles before exhausting its path");
+        let ext_hash = if ext_rest.is_empty() {
+            ext_child
+        } else {
+            self.put_node(Node::Extension {
+                path: ext_rest.to_vec(),
+                child: ext_child,
+            })
+        };
+        children[ext_nibble as usize] = Some(ext_hash);
+
+        let branch_value = match nibbles[common..].split_first() {
+            None => Some(value),
+            Some((&nibble, rest)) => {
+                let hash = self.put_node(Node::Leaf {
+                    path: rest.to_vec(),
+                    value,
+                });
+                children[nibble as usize] = Some(hash);
+                None
+            }
+        };
+
+        let branch_hash = self.put_node(Node::Branch {
+            children,
+            value: branch_value,
+        });
+
+        if common == 0 {
+            branch_hash
+        } else {
+            self.put_node(Node::Extension {
+                path: nibbles[..common].to_vec(),
+                child: branch_hash,
+            })
+        }
+    }
+
+    pub fn remove(&mut self, nibbles: &[u8]) {
+        if let Some(root) = self.root {
+            self.root = self.remove_at(root, nibbles);
+        }
+    }
+
+    fn remove_at(&mut self, node_hash: [u8; 32], nibbles: &[u8]) -> Option<[u8; 32]> {
+        let node = self
+            .nodes
+            .get(&node_hash)
+            .cloned()
+            .expect("trie referenced a node hash that isn't in the store");
+
+        match node {
+            Node::Leaf { path, .. } => (path != nibbles).then_some(node_hash),
+            Node::Extension { path, child } => match nibbles.strip_prefix(path.as_slice()) {
+                None => Some(node_hash),
+                Some(rest) => self
+                    .remove_at(child, rest)
+                    .map(|new_child| self.merge_into_extension(&path, new_child)),
+            },
+            Node::Branch {
+                mut children,
+                value,
+            } => match nibbles.split_first() {
+                None => self.finalize_branch(children, None),
+                Some((&nibble, rest)) => match children[nibble as usize] {
+                    None => Some(node_hash),
+                    Some(child_hash) => {
+                        children[nibble as usize] = self.remove_at(child_hash, rest);
+                        self.finalize_branch(children, value)
+                    }
+                },
+            },
+        }
+    }
+
+    /// Re-attaches `new_child` under `ext_path`, collapsing the extension
+    /// into its child when the child is itself a leaf or extension.
+    fn merge_into_extension(&mut self, ext_path: &[u8], new_child: [u8; 32]) -> [u8; 32] {
+        let child_node = self
+            .nodes
+            .get(&new_child)
+            .cloned()
+            .expect("trie referenced a node hash that isn't in the store");
+
+        match child_node {
+            Node::Leaf { path, value } => {
+                let combined = [ext_path, &path].concat();
+                self.put_node(Node::Leaf {
+                    path: combined,
+                    value,
+                })
+            }
+            Node::Extension { path, child } => {
+                let combined = [ext_path, &path].concat();
+                self.put_node(Node::Extension {
+                    path: combined,
+                    child,
+                })
+            }
+            Node::Branch { .. } => self.put_node(Node::Extension {
+                path: ext_path.to_vec(),
+                child: new_child,
+            }),
+        }
+    }
+
+    /// Collapses a branch that now has too few children/value to justify
+    /// staying a branch: zero children and no value vanishes entirely, and
+    /// exactly one child with no value folds the branch into its child.
+    fn finalize_branch(
+        &mut self,
+        children: [Option<[u8; 32]>; 16],
+        value: Option<Vec<u8>>,
+    ) -> Option<[u8; 32]> {
+        let mut present = children
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| c.map(|h| (i as u8, h)));
+        let first = present.next();
+        let second = present.next();
+        drop(present);
+
+        match (first, second, &value) {
+            (None, _, None) => None,
+            (None, _, Some(v)) => Some(self.put_node(Node::Leaf {
+                path: Vec::new(),
+                value: v.clone(),
+            })),
+            (Some((nibble, child_hash)), None, None) => {
+                Some(self.merge_into_extension(&[nibble], child_hash))
+            }
+            _ => Some(self.put_node(Node::Branch { children, value })),
+        }
+    }
+
+    /// All `(key_nibbles, value)` pairs currently stored, in ascending
+    /// key order (branch children are walked low-nibble-first).
+    pub fn entries(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut out = Vec::new();
+        if let Some(root) = self.root {
+            self.collect(root, Vec::new(), &mut out);
+        }
+        out
+    }
+
+    fn collect(&self, node_hash: [u8; 32], prefix: Vec<u8>, out: &mut Vec<(Vec<u8>, Vec<u8>)>) {
+        let Some(node) = self.nodes.get(&node_hash) else {
+            return;
+        };
+
+        match node {
+            Node::Leaf { path, value } => {
+                let mut key = prefix;
+                key.extend_from_slice(path);
+                out.push((key, value.clone()));
+            }
+            Node::Extension { path, child } => {
+                let mut key = prefix;
+                key.extend_from_slice(path);
+                self.collect(*child, key, out);
+            }
+            Node::Branch { children, value } => {
+                if let Some(v) = value {
+                    out.push((prefix.clone(), v.clone()));
+                }
+                for (nibble, child) in children.iter().enumerate() {
+                    if let Some(hash) = child {
+                        let mut key = prefix.clone();
+                        key.push(nibble as u8);
+                        self.collect(*hash, key, out);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collects the encodings of every node from the root down to where
+    /// `nibbles` either resolves to a value or the path runs out — enough
+    /// for [`verify_proof`] to confirm inclusion or absence against a root.
+    pub fn proof(&self, nibbles: &[u8]) -> Vec<Vec<u8>> {
+        let mut proof = Vec::new();
+        let mut current = self.root;
+        let mut remaining = nibbles;
+
+        while let Some(hash) = current {
+            let Some(node) = self.nodes.get(&hash) else {
+                break;
+            };
+            proof.push(node.encode());
+
+            match node {
+                Node::Leaf { .. } => break,
+                Node::Extension { path, child } => match remaining.strip_prefix(path.as_slice()) {
+                    Some(rest) => {
+                        remaining = rest;
+                        current = Some(*child);
+                    }
+                    None => break,
+                },
+                Node::Branch { children, .. } => match remaining.split_first() {
+                    None => break,
+                    Some((&nibble, rest)) => {
+                        remaining = rest;
+                        current = children[nibble as usize];
+                    }
+                },
+            }
+        }
+
+        proof
+    }
+}
+
+/// Stateless counterpart to [`Trie::proof`]: replays a proof against `root`
+/// and confirms that `nibbles` maps to `expected` (or to nothing, when
+/// `expected` is `None`).
+pub fn verify_proof(
+    root: [u8; 32],
+    nibbles: &[u8],
+    expected: Option<Vec<u8>>,
+    proof: &[Vec<u8>],
+) -> bool {
+    let mut expected_hash = root;
+    let mut remaining = nibbles;
+
+    for encoded in proof {
+        if Sha256::digest(encoded).as_slice() != expected_hash {
+            return false;
+        }
+
+        let Some(node) = Node::decode(encoded) else {
+            return false;
+        };
+
+        match node {
+            Node::Leaf { path, value } => {
+                return if path == remaining {
+                    Some(value) == expected
+                } else {
+                    expected.is_none()
+                };
+            }
+            Node::Extension { path, child } => match remaining.strip_prefix(path.as_slice()) {
+                Some(rest) => {
+                    remaining = rest;
+                    expected_hash = child;
+                }
+                None => return expected.is_none(),
+            },
+            Node::Branch { children, value } => match remaining.split_first() {
+                None => return value == expected,
+                Some((&nibble, rest)) => match children[nibble as usize] {
+                    Some(hash) => {
+                        remaining = rest;
+                        expected_hash = hash;
+                    }
+                    None => return expected.is_none(),
+                },
+            },
+        }
+    }
+
+    // Running out of nodes only proves absence for the empty trie.
+    expected.is_none() && proof.is_empty() && root == [0u8; 32]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nibbles(byte_path: &[u8]) -> Vec<u8> {
+        byte_path.iter().flat_map(|&b| [b >> 4, b & 0x0f]).collect()
+    }
+
+    #[test]
+    fn hex_prefix_round_trips_even_and_odd_paths() {
+        for is_leaf in [true, false] {
+            for path in [vec![], vec![0xa], vec![0x1, 0x2], vec![0x1, 0x2, 0x3]] {
+                let encoded = hp_encode(&path, is_leaf);
+                let (decoded, decoded_is_leaf) = hp_decode(&encoded).unwrap();
+                assert_eq!(decoded, path);
+                assert_eq!(decoded_is_leaf, is_leaf);
+            }
+        }
+    }
+
+    #[test]
+    fn get_returns_inserted_values() {
+        let mut trie = Trie::new();
+        let k1 = nibbles(&[0x12, 0x34]);
+        let k2 = nibbles(&[0x12, 0x35]);
+        let k3 = nibbles(&[0xff]);
+
+        trie.insert(&k1, b"a".to_vec());
+        trie.insert(&k2, b"b".to_vec());
+        trie.insert(&k3, b"c".to_vec());
+
+        assert_eq!(trie.get(&k1), Some(b"a".to_vec()));
+        assert_eq!(trie.get(&k2), Some(b"b".to_vec()));
+        assert_eq!(trie.get(&k3), Some(b"c".to_vec()));
+        assert_eq!(trie.get(&nibbles(&[0x99])), None);
+    }
+
+    #[test]
+    fn insert_is_order_independent() {
+        let keys: Vec<Vec<u8>> = vec![
+            nibbles(&[0x12, 0x34]),
+            nibbles(&[0x12, 0x35]),
+            nibbles(&[0xff]),
+            nibbles(&[0x00]),
+        ];
+
+        let mut forward = Trie::new();
+        for (i, k) in keys.iter().enumerate() {
+            forward.insert(k, vec![i as u8]);
+        }
+
+        let mut backward = Trie::new();
+        for (i, k) in keys.iter().enumerate().rev() {
+            backward.insert(k, vec![i as u8]);
+        }
+
+        assert_eq!(forward.root_hash(), backward.root_hash());
+    }
+
+    #[test]
+    fn remove_restores_previous_root() {
+        let mut trie = Trie::new();
+        let k1 = nibbles(&[0x12, 0x34]);
+        let k2 = nibbles(&[0x12, 0x35]);
+
+        trie.insert(&k1, b"a".to_vec());
+        let root_after_first = trie.root_hash();
+
+        trie.insert(&k2, b"b".to_vec());
+        trie.remove(&k2);
+
+        assert_eq!(trie.root_hash(), root_after_first);
+        assert_eq!(trie.get(&k1), Some(b"a".to_vec()));
+        assert_eq!(trie.get(&k2), None);
+    }
+
+    #[test]
+    fn proof_verifies_inclusion_and_absence() {
+        let mut trie = Trie::new();
+        let k1 = nibbles(&[0x12, 0x34]);
+        let k2 = nibbles(&[0x12, 0x35]);
+        let missing = nibbles(&[0xab, 0xcd]);
+
+        trie.insert(&k1, b"a".to_vec());
+        trie.insert(&k2, b"b".to_vec());
+
+        let root = trie.root_hash();
+
+        let proof1 = trie.proof(&k1);
+        assert!(verify_proof(root, &k1, Some(b"a".to_vec()), &proof1));
+        assert!(!verify_proof(root, &k1, Some(b"b".to_vec()), &proof1));
+
+        let proof_missing = trie.proof(&missing);
+        assert!(verify_proof(root, &missing, None, &proof_missing));
+    }
+}